@@ -18,6 +18,9 @@
 //!
 //! **This command edits your toml files, make sure to have a back up**
 //!
+//! Pass `--dry-run` to preview the change as a unified diff per manifest instead of writing
+//! anything to disk.
+//!
 //! ## Process
 //!
 //! Dependencies can be inherited from a workspace by specifying the dependency in the workspace's [`[workspace.dependencies]`][2] table. After that, add it to the `[dependencies]` table with workspace = true.
@@ -43,17 +46,24 @@
 //! tokio = "1.0"
 //! ```
 //!
+//! Pass `--package-fields` to also hoist identical `[package]` fields (edition, license,
+//! authors, repository, homepage, rust-version, publish) into [`[workspace.package]`][1],
+//! the same way as dependencies. Give it specific field names to restrict which ones are
+//! considered; with none given, the default set above is used.
+//!
 //! [1]: https://doc.rust-lang.org/nightly/cargo/reference/workspaces.html#the-package-table
 //! [2]: https://doc.rust-lang.org/nightly/cargo/reference/workspaces.html#the-dependencies-table
 //! [3]: https://doc.rust-lang.org/nightly/cargo/reference/specifying-dependencies.html#inheriting-a-dependency-from-a-workspace
 
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     path::PathBuf,
 };
 
+use cargo_metadata::semver::{Comparator, Op, Version, VersionReq};
 use clap::Parser;
-use toml_edit::{Document, Formatted, InlineTable, Item, Table, Value};
+use similar::TextDiff;
+use toml_edit::{Array, Document, Formatted, InlineTable, Item, Table, Value};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -70,8 +80,40 @@ struct DependencyInheritor {
     /// Provide the package name as it is defined in by: `[package] name="x"`
     #[clap(long, value_parser)]
     exclude_packages: Vec<String>,
+
+    /// Print a unified diff of every manifest that would change instead of writing to disk.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Name of a `[path-bases]` entry already defined in `.cargo/config.toml` (searched by
+    /// walking up from the workspace root, same as cargo itself does). When set, lifted path
+    /// dependencies that live under that base are written relative to it (with
+    /// `base = "<NAME>"`) instead of relative to the workspace root.
+    #[clap(long, value_parser)]
+    path_base: Option<String>,
+
+    /// Also hoist identical `[package]` fields (edition, license, authors, repository,
+    /// homepage, rust-version, publish) into `[workspace.package]`. Pass one or more field
+    /// names to only consider those; with none given, the default set above is used.
+    #[clap(long, num_args = 0..)]
+    package_fields: Option<Vec<String>>,
 }
 
+/// `[package]` fields considered for `--package-fields` when no field names are given.
+const DEFAULT_PACKAGE_FIELDS: &[&str] = &[
+    "edition",
+    "license",
+    "authors",
+    "repository",
+    "homepage",
+    "rust-version",
+    "publish",
+];
+
+/// Values to add to `[workspace.package]`, and which member manifests had each of those
+/// fields hoisted (keyed by field name and by member manifest path, respectively).
+type HoistedPackageFields = (BTreeMap<String, Item>, BTreeMap<String, Vec<String>>);
+
 #[derive(Parser)]
 #[clap(bin_name = "cargo")]
 enum Cargo {
@@ -91,6 +133,13 @@ fn main() {
 
             let metadata = cmd.exec().unwrap();
 
+            // Resolve the directory the named path-base points at, if any, so lifted path
+            // dependencies can be written relative to it instead of the workspace root.
+            let path_base_dir: Option<PathBuf> = args
+                .path_base
+                .as_ref()
+                .and_then(|name| resolve_path_base_dir(&workspace_path, name));
+
             let exclude_packages: HashSet<String> =
                 HashSet::from_iter(args.exclude_packages.into_iter());
 
@@ -107,7 +156,9 @@ fn main() {
                         .entry(&package_dependency.name)
                         .or_default();
 
-                    detected_dependency.version = package_dependency.req.to_string();
+                    detected_dependency
+                        .version_reqs
+                        .push((package.name.clone(), package_dependency.req.to_string()));
                     detected_dependency.count += 1;
                     detected_dependency
                         .workspace_packages
@@ -115,19 +166,130 @@ fn main() {
                     detected_dependency.no_default_features |=
                         !package_dependency.uses_default_features;
 
-                    detected_dependency.path = package_dependency
-                        .path
-                        .as_ref()
-                        .map(|path| path.strip_prefix(&workspace_path).unwrap().into());
+                    match &package_dependency.path {
+                        Some(path) => {
+                            let (relative_path, relative_to_base) =
+                                resolve_path_dependency(path, &workspace_path, path_base_dir.as_deref());
+                            detected_dependency.path = Some(relative_path);
+                            detected_dependency.path_relative_to_base = relative_to_base;
+                        }
+                        None => detected_dependency.path = None,
+                    }
+
+                    detected_dependency.member_features.insert(
+                        (
+                            package.manifest_path.to_string(),
+                            dependency_type_name(package_dependency.kind).to_string(),
+                        ),
+                        package_dependency.features.iter().cloned().collect(),
+                    );
                 }
             }
 
+            // A feature is only common if every member that depends on the crate requests it;
+            // those go on the workspace entry, everything else stays on the member.
+            for entry in duplicated_dependencies.values_mut() {
+                entry.common_features = common_features(&entry.member_features);
+            }
+
+            // Members don't always agree on a version requirement; reconcile them instead of
+            // letting whichever package the scan visited last silently win.
+            for (&name, entry) in duplicated_dependencies.iter_mut() {
+                entry.version = reconcile_version(name, &entry.version_reqs, &metadata.packages);
+            }
+
             let dependency_candidates = duplicated_dependencies
                 .iter()
                 .filter(|(_, dep)| dep.count >= args.number)
                 .map(|(&name, _)| name.to_owned())
                 .collect();
 
+            // Features a member needs on top of the ones hoisted into the workspace entry,
+            // keyed by (member manifest path, dependency table kind, dependency key).
+            let member_extra_features: BTreeMap<(String, String, String), Vec<String>> =
+                duplicated_dependencies
+                    .iter()
+                    .filter(|(_, entry)| entry.count >= args.number)
+                    .flat_map(|(&name, entry)| {
+                        entry.member_features.iter().filter_map(move |((manifest_path, dependency_type), features)| {
+                            let extra: Vec<String> =
+                                features.difference(&entry.common_features).cloned().collect();
+                            if extra.is_empty() {
+                                None
+                            } else {
+                                Some(((manifest_path.clone(), dependency_type.clone(), name.clone()), extra))
+                            }
+                        })
+                    })
+                    .collect();
+
+            // Gather `[package]` fields that are identical across `n` or more members, mirroring
+            // the dependency-hoisting flow above. Disabled unless --package-fields is passed.
+            let hoisted_package_fields: Option<HoistedPackageFields> =
+                args.package_fields.as_ref().map(|requested| {
+                    let fields: Vec<String> = if requested.is_empty() {
+                        DEFAULT_PACKAGE_FIELDS.iter().map(|s| s.to_string()).collect()
+                    } else {
+                        requested.clone()
+                    };
+
+                    let mut workspace_fields = BTreeMap::<String, Item>::new();
+                    let mut member_hoisted_fields = BTreeMap::<String, Vec<String>>::new();
+
+                    for field in &fields {
+                        // value-key -> (representative toml value, members agreeing on it).
+                        let mut groups = BTreeMap::<String, (Item, Vec<String>)>::new();
+
+                        for package in metadata.workspace_packages() {
+                            if exclude_packages.contains(&package.name) {
+                                continue;
+                            }
+                            if let Some((key, value)) = package_field_value(package, field) {
+                                groups
+                                    .entry(key)
+                                    .or_insert_with(|| (value, Vec::new()))
+                                    .1
+                                    .push(package.manifest_path.to_string());
+                            }
+                        }
+
+                        let Some((_, (value, members))) =
+                            groups.into_iter().max_by_key(|(_, (_, members))| members.len())
+                        else {
+                            continue;
+                        };
+
+                        if members.len() < args.number {
+                            continue;
+                        }
+
+                        workspace_fields.insert(field.clone(), value);
+                        for manifest_path in &members {
+                            member_hoisted_fields
+                                .entry(manifest_path.clone())
+                                .or_default()
+                                .push(field.clone());
+                        }
+
+                        for package in metadata.workspace_packages() {
+                            let manifest_path = package.manifest_path.to_string();
+                            if exclude_packages.contains(&package.name)
+                                || members.contains(&manifest_path)
+                            {
+                                continue;
+                            }
+                            if package_field_value(package, field).is_some() {
+                                eprintln!(
+                                    "warning: '{field}' in {manifest_path} differs from the {} members being hoisted into [workspace.package]; leaving it inline",
+                                    members.len()
+                                );
+                            }
+                        }
+                    }
+
+                    (workspace_fields, member_hoisted_fields)
+                });
+
             // Update the toml definition of the workspace. And add the new 'workspace = true' key value pair.
             for package in metadata.workspace_packages() {
                 let package_toml = &package.manifest_path;
@@ -142,50 +304,27 @@ fn main() {
                     continue;
                 };
 
-                fn rewrite_dependency_table(
-                    dependency_table: &mut Table,
-                    dependency_candidates: &HashSet<String>,
-                ) {
-                    // Iterate all packages with deps that ocurred more then the configured number times.
-                    for (key, val) in dependency_table.iter_mut() {
-                        if !dependency_candidates.contains(key.get()) {
-                            continue;
-                        }
+                // Map each TOML dependency key used by this package to the crate's real name,
+                // so a dependency renamed via `key = { package = "real-name" }` is still
+                // recognised as the same candidate as its unrenamed uses elsewhere.
+                let key_to_name: HashMap<String, String> = package
+                    .dependencies
+                    .iter()
+                    .map(|dep| {
+                        (
+                            dep.rename.clone().unwrap_or_else(|| dep.name.clone()),
+                            dep.name.clone(),
+                        )
+                    })
+                    .collect();
 
-                        match val {
-                            Item::None => todo!(),
-                            Item::Table(table) => {
-                                table.insert("workspace", Item::Value(Value::from(true)));
-                                table.remove("version");
-                                table.remove("path");
-                            }
-                            Item::ArrayOfTables(_) => todo!(),
-                            Item::Value(val) => match val {
-                                Value::InlineTable(table) => {
-                                    // dependency specified as `dep = {version="x"}`.
-
-                                    table.insert("workspace", Value::from(true));
-                                    table.remove("version");
-                                    table.remove("path");
-                                }
-                                Value::String(_) => {
-                                    // dependency specified as `dep = "x"`
-                                    let mut new_table = InlineTable::new();
-                                    new_table.insert("workspace", Value::from(true));
-
-                                    // preserve any line decoration such as comments.
-                                    let decor = val.decor().clone();
-                                    *val = Value::InlineTable(new_table);
-                                    *val.decor_mut() = decor;
-                                }
-                                Value::Integer(_)
-                                | Value::Float(_)
-                                | Value::Boolean(_)
-                                | Value::Datetime(_)
-                                | Value::Array(_) => {
-                                    // dependency not specified in those forms.
-                                }
-                            },
+                let package_manifest_path = package.manifest_path.to_string();
+
+                if let Some((_, member_hoisted_fields)) = &hoisted_package_fields {
+                    if let Some(fields) = member_hoisted_fields.get(&package_manifest_path) {
+                        if let Some(Item::Table(package_table)) = toml_document.get_mut("package")
+                        {
+                            rewrite_package_table(package_table, fields);
                         }
                     }
                 }
@@ -195,22 +334,39 @@ fn main() {
                     if let Some(Item::Table(dependency_table)) =
                         toml_document.get_mut(dependency_type)
                     {
-                        rewrite_dependency_table(dependency_table, &dependency_candidates)
+                        rewrite_dependency_table(
+                            dependency_table,
+                            &dependency_candidates,
+                            &member_extra_features,
+                            &key_to_name,
+                            &package_manifest_path,
+                            dependency_type,
+                        )
                     }
                     if let Some(Item::Table(target)) = toml_document.get_mut("target") {
                         for (_name, cfg) in target.iter_mut() {
                             if let Some(Item::Table(dependency_table)) =
                                 cfg.get_mut(dependency_type)
                             {
-                                rewrite_dependency_table(dependency_table, &dependency_candidates)
+                                rewrite_dependency_table(
+                                    dependency_table,
+                                    &dependency_candidates,
+                                    &member_extra_features,
+                                    &key_to_name,
+                                    &package_manifest_path,
+                                    dependency_type,
+                                )
                             }
                         }
                     }
                 }
 
-                if let Err(e) = std::fs::write(package_toml, toml_document.to_string()) {
-                    eprintln!("Failed to write to {package_toml:?}: {e:?}");
-                }
+                apply_toml_changes(
+                    package_toml,
+                    &toml_contents,
+                    toml_document.to_string(),
+                    args.dry_run,
+                );
             }
 
             // Print the results.
@@ -230,11 +386,19 @@ fn main() {
                         &mut doc,
                         &duplicated_dependencies,
                         args.number,
+                        args.path_base.as_deref(),
                     );
 
-                    if let Err(e) = std::fs::write(&args.workspace_path, doc.to_string()) {
-                        eprintln!("Failed to write to {:?}: {:?}", args.workspace_path, e);
+                    if let Some((workspace_fields, _)) = &hoisted_package_fields {
+                        edit_workspace_package_table(&mut doc, workspace_fields);
                     }
+
+                    apply_toml_changes(
+                        &args.workspace_path,
+                        &toml_contents,
+                        doc.to_string(),
+                        args.dry_run,
+                    );
                 } else {
                     println!("failed to parse workspace definition");
                 };
@@ -245,16 +409,215 @@ fn main() {
     }
 }
 
+/// Find the directory a `[path-bases]` entry named `name` points at, by walking up from
+/// `workspace_root` looking for `.cargo/config.toml` (falling back to the legacy `.cargo/config`
+/// filename), the same config-discovery order cargo itself uses. Paths inside `[path-bases]`
+/// are relative to the directory containing the config file that defines them.
+fn resolve_path_base_dir(workspace_root: &std::path::Path, name: &str) -> Option<PathBuf> {
+    for dir in workspace_root.ancestors() {
+        for config_file_name in [".cargo/config.toml", ".cargo/config"] {
+            let config_path = dir.join(config_file_name);
+            let Ok(contents) = std::fs::read_to_string(&config_path) else {
+                continue;
+            };
+            let Ok(doc) = contents.parse::<Document>() else {
+                continue;
+            };
+            if let Some(base_path) = doc.get("path-bases").and_then(|t| t.get(name)).and_then(|v| v.as_str()) {
+                return Some(dir.join(base_path));
+            }
+        }
+    }
+    None
+}
+
+/// Make `path` relative to `path_base_dir` if it's given and `path` actually lives under it;
+/// otherwise fall back to making it relative to the workspace root. Returns whether the base
+/// was used, so callers know whether it's still correct to emit `base = "<NAME>"` for this path.
+fn resolve_path_dependency(
+    path: &cargo_metadata::camino::Utf8Path,
+    workspace_root: &std::path::Path,
+    path_base_dir: Option<&std::path::Path>,
+) -> (PathBuf, bool) {
+    if let Some(base) = path_base_dir {
+        if let Ok(relative) = path.strip_prefix(base) {
+            return (relative.into(), true);
+        }
+    }
+    (path.strip_prefix(workspace_root).unwrap_or(path).into(), false)
+}
+
+/// Write `updated` to `path`, unless `dry_run` is set, in which case a unified diff against
+/// `original` is printed instead. No-ops (and prints nothing) if nothing actually changed.
+fn apply_toml_changes<P: AsRef<std::path::Path>>(
+    path: P,
+    original: &str,
+    updated: String,
+    dry_run: bool,
+) {
+    if original == updated {
+        return;
+    }
+
+    let path = path.as_ref();
+
+    if dry_run {
+        let label = path.display().to_string();
+        let diff = TextDiff::from_lines(original, &updated)
+            .unified_diff()
+            .header(&label, &label)
+            .to_string();
+        print!("{diff}");
+    } else if let Err(e) = std::fs::write(path, updated) {
+        eprintln!("Failed to write to {path:?}: {e:?}");
+    }
+}
+
+/// Maps a `cargo_metadata` dependency kind to the TOML table name it's declared in.
+fn dependency_type_name(kind: cargo_metadata::DependencyKind) -> &'static str {
+    match kind {
+        cargo_metadata::DependencyKind::Development => "dev-dependencies",
+        cargo_metadata::DependencyKind::Build => "build-dependencies",
+        _ => "dependencies",
+    }
+}
+
+/// Features every member agrees on for a single dependency, scoped per dependency-table kind
+/// so an unrelated `[dev-dependencies]` feature can't suppress hoisting a feature every
+/// `[dependencies]` use already agrees on. Cargo only gives a hoisted dependency one features
+/// list though, so once each kind's common set is computed, pick whichever kind is most
+/// representative, preferring the main `[dependencies]` table.
+fn common_features(member_features: &BTreeMap<(String, String), BTreeSet<String>>) -> BTreeSet<String> {
+    let mut common_by_kind = BTreeMap::<&str, BTreeSet<String>>::new();
+    for ((_, dependency_type), features) in member_features {
+        common_by_kind
+            .entry(dependency_type.as_str())
+            .and_modify(|common: &mut BTreeSet<String>| {
+                *common = common.intersection(features).cloned().collect();
+            })
+            .or_insert_with(|| features.clone());
+    }
+    ["dependencies", "dev-dependencies", "build-dependencies"]
+        .into_iter()
+        .find_map(|kind| common_by_kind.get(kind))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Rewrite every entry in `dependency_table` that resolves (through `key_to_name`, which
+/// accounts for `package = "..."` renames) to one of the `dependency_candidates` into
+/// `workspace = true` form. `dependency_type` (e.g. `"dependencies"`, `"dev-dependencies"`)
+/// identifies which table this is, since the same crate can appear in more than one with
+/// different features.
+fn rewrite_dependency_table(
+    dependency_table: &mut Table,
+    dependency_candidates: &HashSet<String>,
+    member_extra_features: &BTreeMap<(String, String, String), Vec<String>>,
+    key_to_name: &HashMap<String, String>,
+    package_manifest_path: &str,
+    dependency_type: &str,
+) {
+    // Iterate all packages with deps that ocurred more then the configured number times.
+    for (key, val) in dependency_table.iter_mut() {
+        let Some(real_name) = key_to_name.get(key.get()) else {
+            continue;
+        };
+        if !dependency_candidates.contains(real_name) {
+            continue;
+        }
+
+        let extra_features = member_extra_features.get(&(
+            package_manifest_path.to_owned(),
+            dependency_type.to_owned(),
+            real_name.clone(),
+        ));
+
+        match val {
+            Item::None => todo!(),
+            Item::Table(table) => {
+                table.insert("workspace", Item::Value(Value::from(true)));
+                table.remove("version");
+                table.remove("path");
+                // Cargo forbids overriding `default-features` on a workspace = true
+                // dependency; that setting now lives only in the workspace table.
+                table.remove("default-features");
+                match extra_features {
+                    Some(features) => {
+                        table.insert(
+                            "features",
+                            Item::Value(Value::from(Array::from_iter(features.clone()))),
+                        );
+                    }
+                    None => {
+                        table.remove("features");
+                    }
+                }
+            }
+            Item::ArrayOfTables(_) => todo!(),
+            Item::Value(val) => match val {
+                Value::InlineTable(table) => {
+                    // dependency specified as `dep = {version="x"}`.
+
+                    table.insert("workspace", Value::from(true));
+                    table.remove("version");
+                    table.remove("path");
+                    table.remove("default-features");
+                    match extra_features {
+                        Some(features) => {
+                            table.insert(
+                                "features",
+                                Value::from(Array::from_iter(features.clone())),
+                            );
+                        }
+                        None => {
+                            table.remove("features");
+                        }
+                    }
+                }
+                Value::String(_) => {
+                    // dependency specified as `dep = "x"`
+                    let mut new_table = InlineTable::new();
+                    new_table.insert("workspace", Value::from(true));
+                    if let Some(features) = extra_features {
+                        new_table.insert(
+                            "features",
+                            Value::from(Array::from_iter(features.clone())),
+                        );
+                    }
+
+                    // preserve any line decoration such as comments.
+                    let decor = val.decor().clone();
+                    *val = Value::InlineTable(new_table);
+                    *val.decor_mut() = decor;
+                }
+                Value::Integer(_)
+                | Value::Float(_)
+                | Value::Boolean(_)
+                | Value::Datetime(_)
+                | Value::Array(_) => {
+                    // dependency not specified in those forms.
+                }
+            },
+        }
+    }
+}
+
 fn edit_workspace_dependency_table(
     document: &mut Document,
     workspace_deps: &BTreeMap<&String, Entry>,
     occurrences: usize,
+    path_base: Option<&str>,
 ) {
-    // Crate table if not exist, otherwise edit.
-    if let Some(Item::Table(table)) = document.get_mut("workspace.dependencies") {
+    let existing_dependency_table = document
+        .get_mut("workspace")
+        .and_then(Item::as_table_mut)
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(Item::as_table_mut);
+
+    if let Some(table) = existing_dependency_table {
         for (key, val) in workspace_deps {
             if val.count >= occurrences && !table.contains_key(key.as_str()) {
-                table.insert(key, val.to_toml());
+                table.insert(key, val.to_toml(path_base));
             }
         }
     } else {
@@ -262,7 +625,7 @@ fn edit_workspace_dependency_table(
 
         for (key, val) in workspace_deps {
             if val.count >= occurrences {
-                new_table.insert(key, val.to_toml());
+                new_table.insert(key, val.to_toml(path_base));
             }
         }
 
@@ -270,33 +633,642 @@ fn edit_workspace_dependency_table(
     }
 }
 
+/// Rewrite each of `hoisted_fields` present in a member's `[package]` table into the
+/// `field = { workspace = true }` form, the same convention already used for dependencies.
+fn rewrite_package_table(package_table: &mut Table, hoisted_fields: &[String]) {
+    for field in hoisted_fields {
+        if package_table.contains_key(field) {
+            let mut workspace_ref = InlineTable::new();
+            workspace_ref.insert("workspace", Value::from(true));
+            package_table.insert(field, Item::Value(Value::InlineTable(workspace_ref)));
+        }
+    }
+}
+
+/// Add any of `package_fields` that aren't already present to the workspace's
+/// `[workspace.package]` table, creating it if needed.
+fn edit_workspace_package_table(document: &mut Document, package_fields: &BTreeMap<String, Item>) {
+    if package_fields.is_empty() {
+        return;
+    }
+
+    let existing_package_table = document
+        .get_mut("workspace")
+        .and_then(Item::as_table_mut)
+        .and_then(|workspace| workspace.get_mut("package"))
+        .and_then(Item::as_table_mut);
+
+    if let Some(table) = existing_package_table {
+        for (field, value) in package_fields {
+            if !table.contains_key(field) {
+                table.insert(field, value.clone());
+            }
+        }
+    } else {
+        let mut new_table = Table::new();
+        for (field, value) in package_fields {
+            new_table.insert(field, value.clone());
+        }
+        document["workspace"]["package"] = Item::Table(new_table);
+    }
+}
+
+/// Extract a `[package]` field's value from the resolved metadata, along with a canonical
+/// string key used to group members that agree on it. Returns `None` if the package doesn't
+/// set the field, or if `field` isn't one this tool knows how to hoist.
+fn package_field_value(package: &cargo_metadata::Package, field: &str) -> Option<(String, Item)> {
+    match field {
+        "edition" => {
+            let edition = package.edition.to_string();
+            Some((edition.clone(), Item::Value(Value::from(edition))))
+        }
+        "license" => package
+            .license
+            .clone()
+            .map(|license| (license.clone(), Item::Value(Value::from(license)))),
+        "authors" if !package.authors.is_empty() => {
+            let key = package.authors.join(",");
+            let authors = Array::from_iter(package.authors.iter().cloned());
+            Some((key, Item::Value(Value::from(authors))))
+        }
+        "repository" => package
+            .repository
+            .clone()
+            .map(|repository| (repository.clone(), Item::Value(Value::from(repository)))),
+        "homepage" => package
+            .homepage
+            .clone()
+            .map(|homepage| (homepage.clone(), Item::Value(Value::from(homepage)))),
+        "rust-version" => package.rust_version.as_ref().map(|rust_version| {
+            let rust_version = rust_version.to_string();
+            (rust_version.clone(), Item::Value(Value::from(rust_version)))
+        }),
+        "publish" => package.publish.clone().map(|registries| {
+            if registries.is_empty() {
+                ("false".to_string(), Item::Value(Value::from(false)))
+            } else {
+                let key = registries.join(",");
+                (key, Item::Value(Value::from(Array::from_iter(registries))))
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// Pick a single version requirement for a dependency that members disagree on.
+///
+/// Prefers the requirement whose allowed set is a superset of every other requirement seen;
+/// if the requirements are incomparable, falls back to whichever matches the newest version
+/// of the crate actually resolved in the dependency graph. Either way, a warning is printed
+/// so the user can double check the result.
+fn reconcile_version(
+    name: &str,
+    version_reqs: &[(String, String)],
+    resolved_packages: &[cargo_metadata::Package],
+) -> String {
+    let mut distinct = Vec::<&String>::new();
+    for (_, req) in version_reqs {
+        // "*" means "no constraint"; it never conflicts with anything.
+        if req != "*" && !distinct.contains(&req) {
+            distinct.push(req);
+        }
+    }
+
+    if distinct.len() <= 1 {
+        return distinct.first().map(|req| (*req).clone()).unwrap_or_else(|| "*".to_string());
+    }
+
+    let parsed: Vec<(&String, VersionReq)> = distinct
+        .iter()
+        .filter_map(|req| VersionReq::parse(req).ok().map(|parsed| (*req, parsed)))
+        .collect();
+
+    // `comparator_floor` only gives a meaningful floor for inclusive comparators (`^`, `~`,
+    // `=`, `>=`, `<=`); for an exclusive one (`>`, `<`) the literal version is excluded, not a
+    // valid floor, so the "does every requirement's allowed set contain every floor" check
+    // below can't be trusted and we go straight to the incomparable fallback instead.
+    let has_exclusive_comparator = parsed
+        .iter()
+        .any(|(_, req)| req.comparators.iter().any(|c| matches!(c.op, Op::Less | Op::Greater)));
+
+    let comparator_versions: Vec<Version> = parsed
+        .iter()
+        .flat_map(|(_, req)| req.comparators.iter())
+        .map(comparator_floor)
+        .collect();
+
+    let chosen = if has_exclusive_comparator {
+        None
+    } else {
+        parsed
+            .iter()
+            .find(|(_, candidate)| comparator_versions.iter().all(|v| candidate.matches(v)))
+            .map(|(req, _)| (*req).clone())
+    }
+    .or_else(|| {
+            // Incomparable: defer to whichever requirement matches the newest version of
+            // this crate that is actually resolved in the workspace's dependency graph.
+            resolved_packages
+                .iter()
+                .filter(|package| package.name == name)
+                .map(|package| &package.version)
+                .max()
+                .and_then(|version| {
+                    parsed
+                        .iter()
+                        .find(|(_, req)| req.matches(version))
+                        .map(|(req, _)| (*req).clone())
+                })
+        })
+        .unwrap_or_else(|| (*distinct.last().expect("distinct has at least 2 entries")).clone());
+
+    eprintln!("warning: conflicting version requirements for '{name}', using '{chosen}':");
+    for (member, req) in version_reqs {
+        if req != "*" {
+            eprintln!("  - {member}: {req}");
+        }
+    }
+
+    chosen
+}
+
+/// The lowest concrete version a comparator could match, used only to probe whether one
+/// requirement's allowed set contains another's.
+fn comparator_floor(comparator: &Comparator) -> Version {
+    Version {
+        major: comparator.major,
+        minor: comparator.minor.unwrap_or(0),
+        patch: comparator.patch.unwrap_or(0),
+        pre: comparator.pre.clone(),
+        build: Default::default(),
+    }
+}
+
 #[derive(Default)]
 struct Entry {
     pub count: usize,
     pub workspace_packages: Vec<String>,
     pub version: String,
+    /// Every version requirement seen for this dependency, as (member package name, requirement).
+    pub version_reqs: Vec<(String, String)>,
     pub path: Option<PathBuf>,
+    /// Whether `path` (when set) was made relative to `--path-base`'s directory; if `false`,
+    /// `path` is relative to the workspace root instead, and `base` must not be emitted for it.
+    pub path_relative_to_base: bool,
     /// Whether _any_ package uses this crate with the default features _enabled_
     pub no_default_features: bool,
+    /// Features requested by each member, keyed by (that member's manifest path, the
+    /// dependency table kind it was requested in, e.g. `"dependencies"`/`"dev-dependencies"`).
+    pub member_features: BTreeMap<(String, String), BTreeSet<String>>,
+    /// Features every member requesting this dependency agrees on; hoisted into the
+    /// workspace entry so members only need to list what they uniquely need.
+    pub common_features: BTreeSet<String>,
 }
 
 impl Entry {
-    fn to_toml(&self) -> Item {
+    fn to_toml(&self, path_base: Option<&str>) -> Item {
         let version = Value::String(Formatted::new(self.version.clone()));
-        Item::Value(if self.no_default_features || self.path.is_some() {
-            let mut itable = InlineTable::new();
-            if self.version != "*" {
-                itable.insert("version", version);
-            }
-            if let Some(path) = &self.path {
-                itable.insert("path", Value::from(path.to_str().unwrap()));
-            }
-            if self.no_default_features {
-                itable.insert("default-features", Value::from(false));
-            }
-            Value::InlineTable(itable)
-        } else {
-            version
-        })
+        Item::Value(
+            if self.no_default_features || self.path.is_some() || !self.common_features.is_empty()
+            {
+                let mut itable = InlineTable::new();
+                if self.version != "*" {
+                    itable.insert("version", version);
+                }
+                if let Some(path) = &self.path {
+                    if let Some(base) = path_base {
+                        if self.path_relative_to_base {
+                            itable.insert("base", Value::from(base));
+                        }
+                    }
+                    itable.insert("path", Value::from(path.to_str().unwrap()));
+                }
+                if self.no_default_features {
+                    itable.insert("default-features", Value::from(false));
+                }
+                if !self.common_features.is_empty() {
+                    itable.insert(
+                        "features",
+                        Value::from(Array::from_iter(self.common_features.iter().cloned())),
+                    );
+                }
+                Value::InlineTable(itable)
+            } else {
+                version
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_dependency_table_handles_renamed_dependency() {
+        let mut document = r#"
+[dependencies]
+foo = { package = "real-name", version = "1" }
+bar = "2"
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let dependency_candidates = HashSet::from(["real-name".to_string(), "bar".to_string()]);
+        let key_to_name = HashMap::from([
+            ("foo".to_string(), "real-name".to_string()),
+            ("bar".to_string(), "bar".to_string()),
+        ]);
+        let member_extra_features = BTreeMap::new();
+
+        let Item::Table(dependency_table) = document.get_mut("dependencies").unwrap() else {
+            panic!("expected a table");
+        };
+
+        rewrite_dependency_table(
+            dependency_table,
+            &dependency_candidates,
+            &member_extra_features,
+            &key_to_name,
+            "member/Cargo.toml",
+            "dependencies",
+        );
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"foo = { package = "real-name", workspace = true }"#));
+        assert!(rewritten.contains(r#"bar = { workspace = true }"#));
+    }
+
+    #[test]
+    fn rewrite_dependency_table_ignores_rename_that_is_not_a_candidate() {
+        // "foo" is renamed to "other-crate", which never occurred often enough to be
+        // inherited, so the table must be left untouched.
+        let mut document = r#"
+[dependencies]
+foo = { package = "other-crate", version = "1" }
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let dependency_candidates = HashSet::from(["real-name".to_string()]);
+        let key_to_name = HashMap::from([("foo".to_string(), "other-crate".to_string())]);
+        let member_extra_features = BTreeMap::new();
+
+        let Item::Table(dependency_table) = document.get_mut("dependencies").unwrap() else {
+            panic!("expected a table");
+        };
+
+        rewrite_dependency_table(
+            dependency_table,
+            &dependency_candidates,
+            &member_extra_features,
+            &key_to_name,
+            "member/Cargo.toml",
+            "dependencies",
+        );
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"version = "1""#));
+        assert!(!rewritten.contains("workspace"));
+    }
+
+    #[test]
+    fn rewrite_dependency_table_keeps_features_separate_per_dependency_kind() {
+        // Same crate, different features in `[dependencies]` vs `[dev-dependencies]`; each
+        // table must only gain the features that were actually declared in it.
+        let mut dependencies_doc = "[dependencies]\nfoo = { version = \"1\" }\n"
+            .parse::<Document>()
+            .unwrap();
+        let mut dev_dependencies_doc = "[dev-dependencies]\nfoo = { version = \"1\" }\n"
+            .parse::<Document>()
+            .unwrap();
+
+        let dependency_candidates = HashSet::from(["foo".to_string()]);
+        let key_to_name = HashMap::from([("foo".to_string(), "foo".to_string())]);
+        let member_extra_features = BTreeMap::from([
+            (
+                ("member/Cargo.toml".to_string(), "dependencies".to_string(), "foo".to_string()),
+                vec!["inline".to_string()],
+            ),
+            (
+                (
+                    "member/Cargo.toml".to_string(),
+                    "dev-dependencies".to_string(),
+                    "foo".to_string(),
+                ),
+                vec!["testing".to_string()],
+            ),
+        ]);
+
+        let Item::Table(dependencies_table) = dependencies_doc.get_mut("dependencies").unwrap()
+        else {
+            panic!("expected a table");
+        };
+        rewrite_dependency_table(
+            dependencies_table,
+            &dependency_candidates,
+            &member_extra_features,
+            &key_to_name,
+            "member/Cargo.toml",
+            "dependencies",
+        );
+
+        let Item::Table(dev_dependencies_table) =
+            dev_dependencies_doc.get_mut("dev-dependencies").unwrap()
+        else {
+            panic!("expected a table");
+        };
+        rewrite_dependency_table(
+            dev_dependencies_table,
+            &dependency_candidates,
+            &member_extra_features,
+            &key_to_name,
+            "member/Cargo.toml",
+            "dev-dependencies",
+        );
+
+        let rewritten_dependencies = dependencies_doc.to_string();
+        let rewritten_dev_dependencies = dev_dependencies_doc.to_string();
+        assert!(rewritten_dependencies.contains(r#"features = ["inline"]"#));
+        assert!(!rewritten_dependencies.contains("testing"));
+        assert!(rewritten_dev_dependencies.contains(r#"features = ["testing"]"#));
+        assert!(!rewritten_dev_dependencies.contains("inline"));
+    }
+
+    #[test]
+    fn common_features_is_not_suppressed_by_an_unrelated_dependency_kind() {
+        // All three members agree on "inline" in `[dependencies]`; one member also has an
+        // unrelated `[dev-dependencies]` use with a different feature. That dev-dependency
+        // feature must not empty out the `[dependencies]` common set.
+        let member_features = BTreeMap::from([
+            (
+                ("a/Cargo.toml".to_string(), "dependencies".to_string()),
+                BTreeSet::from(["inline".to_string()]),
+            ),
+            (
+                ("a/Cargo.toml".to_string(), "dev-dependencies".to_string()),
+                BTreeSet::from(["unicode".to_string()]),
+            ),
+            (
+                ("b/Cargo.toml".to_string(), "dependencies".to_string()),
+                BTreeSet::from(["inline".to_string()]),
+            ),
+            (
+                ("c/Cargo.toml".to_string(), "dependencies".to_string()),
+                BTreeSet::from(["inline".to_string()]),
+            ),
+        ]);
+
+        assert_eq!(
+            common_features(&member_features),
+            BTreeSet::from(["inline".to_string()])
+        );
+    }
+
+    #[test]
+    fn reconcile_version_picks_the_requirement_that_is_a_superset() {
+        let version_reqs = [
+            ("a".to_string(), "1.2".to_string()),
+            ("b".to_string(), "1".to_string()),
+        ];
+        assert_eq!(reconcile_version("dep", &version_reqs, &[]), "1");
+    }
+
+    #[test]
+    fn reconcile_version_falls_back_to_newest_resolved_version_when_incomparable() {
+        let version_reqs = [
+            ("a".to_string(), "1.0".to_string()),
+            ("b".to_string(), "2.0".to_string()),
+        ];
+        let resolved_packages = [
+            make_resolved_package("dep", "1.0.5"),
+            make_resolved_package("dep", "2.0.3"),
+        ];
+        assert_eq!(
+            reconcile_version("dep", &version_reqs, &resolved_packages),
+            "2.0"
+        );
+    }
+
+    #[test]
+    fn reconcile_version_does_not_treat_an_exclusive_bound_as_a_floor() {
+        // ">1.5.0" excludes 1.5.0 itself, so "1.2" (">=1.2.0,<2.0.0") is NOT a superset of it
+        // even though "1.2" trivially "matches" the literal 1.5.0 floor. The naive floor check
+        // must not pick "1.2" here; it should fall back to the resolved-version heuristic.
+        let version_reqs = [
+            ("a".to_string(), ">1.5.0".to_string()),
+            ("b".to_string(), "1.2".to_string()),
+        ];
+        let resolved_packages = [make_resolved_package("dep", "1.9.0")];
+        assert_eq!(
+            reconcile_version("dep", &version_reqs, &resolved_packages),
+            ">1.5.0"
+        );
+    }
+
+    fn make_resolved_package(name: &str, version: &str) -> cargo_metadata::Package {
+        let metadata = cargo_metadata::MetadataCommand::parse(format!(
+            r#"{{
+                "packages": [{{
+                    "name": "{name}",
+                    "version": "{version}",
+                    "id": "{name} {version} (path+file:///tmp/{name})",
+                    "dependencies": [],
+                    "targets": [],
+                    "features": {{}},
+                    "manifest_path": "/tmp/{name}/Cargo.toml",
+                    "authors": [],
+                    "categories": [],
+                    "keywords": [],
+                    "readme": null,
+                    "repository": null,
+                    "homepage": null,
+                    "documentation": null,
+                    "edition": "2021",
+                    "links": null,
+                    "default_run": null,
+                    "rust_version": null,
+                    "metadata": null,
+                    "publish": null,
+                    "source": null,
+                    "description": null,
+                    "license": null,
+                    "license_file": null
+                }}],
+                "workspace_members": [],
+                "resolve": null,
+                "workspace_root": "/tmp",
+                "target_directory": "/tmp/target",
+                "metadata": null,
+                "version": 1
+            }}"#
+        ))
+        .unwrap();
+        metadata.packages.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn edit_workspace_package_table_merges_into_existing_table() {
+        let mut document = r#"
+[workspace.package]
+description = "a crate"
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let package_fields =
+            BTreeMap::from([("edition".to_string(), Item::Value(Value::from("2021")))]);
+
+        edit_workspace_package_table(&mut document, &package_fields);
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"description = "a crate""#));
+        assert!(rewritten.contains(r#"edition = "2021""#));
+    }
+
+    #[test]
+    fn edit_workspace_package_table_creates_table_when_missing() {
+        let mut document = "[workspace]\nmembers = [\"a\"]\n".parse::<Document>().unwrap();
+
+        let package_fields =
+            BTreeMap::from([("edition".to_string(), Item::Value(Value::from("2021")))]);
+
+        edit_workspace_package_table(&mut document, &package_fields);
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"edition = "2021""#));
+    }
+
+    #[test]
+    fn edit_workspace_dependency_table_merges_into_existing_table() {
+        let mut document = r#"
+[workspace]
+members = ["a"]
+
+[workspace.dependencies]
+preexisting-dep = "9.9"
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let new_dep_name = "similar".to_string();
+        let new_dep = Entry {
+            count: 2,
+            version: "1.0".to_string(),
+            ..Default::default()
+        };
+        let workspace_deps = BTreeMap::from([(&new_dep_name, new_dep)]);
+
+        edit_workspace_dependency_table(&mut document, &workspace_deps, 2, None);
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"preexisting-dep = "9.9""#));
+        assert!(rewritten.contains(r#"similar = "1.0""#));
+    }
+
+    #[test]
+    fn edit_workspace_dependency_table_creates_table_when_missing() {
+        let mut document = "[workspace]\nmembers = [\"a\"]\n".parse::<Document>().unwrap();
+
+        let new_dep_name = "similar".to_string();
+        let new_dep = Entry {
+            count: 2,
+            version: "1.0".to_string(),
+            ..Default::default()
+        };
+        let workspace_deps = BTreeMap::from([(&new_dep_name, new_dep)]);
+
+        edit_workspace_dependency_table(&mut document, &workspace_deps, 2, None);
+
+        let rewritten = document.to_string();
+        assert!(rewritten.contains(r#"similar = "1.0""#));
+    }
+
+    #[test]
+    fn apply_toml_changes_does_not_touch_disk_in_dry_run() {
+        let path = std::env::temp_dir().join(format!(
+            "cargo-dependency-inheritor-test-dry-run-{:?}.toml",
+            std::thread::current().id()
+        ));
+        std::fs::write(&path, "original = true\n").unwrap();
+
+        apply_toml_changes(&path, "original = true\n", "updated = true\n".to_string(), true);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "original = true\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn apply_toml_changes_writes_file_when_not_dry_run() {
+        let path = std::env::temp_dir().join(format!(
+            "cargo-dependency-inheritor-test-write-{:?}.toml",
+            std::thread::current().id()
+        ));
+        std::fs::write(&path, "original = true\n").unwrap();
+
+        apply_toml_changes(&path, "original = true\n", "updated = true\n".to_string(), false);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "updated = true\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn apply_toml_changes_is_a_noop_when_nothing_changed() {
+        // Use a path that doesn't exist; if this were (incorrectly) written to, the write
+        // would fail and the test would panic.
+        let path = std::env::temp_dir().join(format!(
+            "cargo-dependency-inheritor-test-noop-{:?}/Cargo.toml",
+            std::thread::current().id()
+        ));
+
+        apply_toml_changes(&path, "same = true\n", "same = true\n".to_string(), false);
+        apply_toml_changes(&path, "same = true\n", "same = true\n".to_string(), true);
+    }
+
+    #[test]
+    fn resolve_path_base_dir_reads_cargo_config_toml() {
+        let dir = std::env::temp_dir().join(format!(
+            "cargo-dependency-inheritor-test-{:?}",
+            std::thread::current().id()
+        ));
+        let cargo_dir = dir.join(".cargo");
+        std::fs::create_dir_all(&cargo_dir).unwrap();
+        std::fs::write(
+            cargo_dir.join("config.toml"),
+            "[path-bases]\nshared = \"shared\"\n",
+        )
+        .unwrap();
+
+        let resolved = resolve_path_base_dir(&dir, "shared");
+        assert_eq!(resolved, Some(dir.join("shared")));
+        assert_eq!(resolve_path_base_dir(&dir, "missing"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_path_dependency_falls_back_when_outside_base() {
+        let workspace_root = std::path::Path::new("/workspace");
+        let base = std::path::Path::new("/workspace/shared");
+
+        // Under the base: relative to it, and the base is still applicable.
+        let under_base =
+            cargo_metadata::camino::Utf8Path::new("/workspace/shared/libcore");
+        assert_eq!(
+            resolve_path_dependency(under_base, workspace_root, Some(base)),
+            (PathBuf::from("libcore"), true)
+        );
+
+        // Outside the base: falls back to workspace-root-relative, and the base no longer
+        // applies to this dependency.
+        let outside_base = cargo_metadata::camino::Utf8Path::new("/workspace/other/libextra");
+        assert_eq!(
+            resolve_path_dependency(outside_base, workspace_root, Some(base)),
+            (PathBuf::from("other/libextra"), false)
+        );
     }
 }